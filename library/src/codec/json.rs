@@ -0,0 +1,42 @@
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Codec;
+
+/// The JSON codec, backed by `serde_json`.
+pub struct JsonCodec<T>(PhantomData<T>);
+
+impl<T> JsonCodec<T> {
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for JsonCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for JsonCodec<T> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Copy for JsonCodec<T> {}
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec<T> {
+    type Error = serde_json::Error;
+
+    const CONTENT_TYPE: &'static str = "application/json";
+
+    fn encode(&self, value: &T) -> Vec<u8> {
+        serde_json::to_vec(value).expect("T must be representable as JSON")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, Self::Error> {
+        serde_json::from_slice(bytes)
+    }
+}
@@ -0,0 +1,7 @@
+//! [`Codec`](crate::Codec) implementations shipped with this crate.
+
+pub mod json;
+pub mod preserves;
+
+pub use json::JsonCodec;
+pub use preserves::PreservesCodec;
@@ -0,0 +1,906 @@
+//! A self-describing binary codec modeled on [Preserves](https://preserves.dev).
+//!
+//! Messages carry their own structure on the wire: a [`Value`] distinguishes
+//! integers, byte strings, UTF-8 strings, sequences, dictionaries and
+//! labeled records, so a message can be decoded and inspected without the
+//! receiver knowing the concrete Rust type in advance. [`PreservesCodec`]
+//! bridges this `Value` tree to any `T: Serialize + DeserializeOwned` via
+//! `serde`, the same way `serde_json::Value` bridges to JSON.
+
+use std::marker::PhantomData;
+
+use serde::{
+    de::{DeserializeOwned, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor},
+    ser::{
+        SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use thiserror::Error;
+
+use crate::Codec;
+
+/// A value in the Preserves-inspired data model used by [`PreservesCodec`].
+///
+/// Unlike a JSON value, a [`Value::Record`] carries a label distinct from
+/// its fields, so a message can be tagged with its shape (e.g. a struct or
+/// enum variant name) independently of the data itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    String(String),
+    Sequence(Vec<Value>),
+    Dictionary(Vec<(Value, Value)>),
+    Record { label: String, fields: Vec<Value> },
+}
+
+#[derive(Debug, Error)]
+pub enum PreservesError {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("unknown tag byte: {0}")]
+    UnknownTag(u8),
+    #[error("string is not valid UTF-8: {0}")]
+    InvalidUtf8(#[from] std::string::FromUtf8Error),
+    #[error("trailing bytes after value")]
+    TrailingBytes,
+    #[error("value cannot be represented in the Preserves data model: {0}")]
+    Unrepresentable(&'static str),
+    #[error("unexpected value shape: {0}")]
+    UnexpectedShape(&'static str),
+    #[error("{0}")]
+    Message(String),
+}
+
+impl serde::ser::Error for PreservesError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for PreservesError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+const TAG_INTEGER: u8 = 1;
+const TAG_BYTES: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_SEQUENCE: u8 = 4;
+const TAG_DICTIONARY: u8 = 5;
+const TAG_RECORD: u8 = 6;
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn read_len(bytes: &[u8]) -> Result<(usize, &[u8]), PreservesError> {
+    let (len_bytes, rest) = split_at(bytes, 4)?;
+    let len = u32::from_le_bytes(len_bytes.try_into().unwrap());
+    Ok((len as usize, rest))
+}
+
+fn split_at(bytes: &[u8], mid: usize) -> Result<(&[u8], &[u8]), PreservesError> {
+    if bytes.len() < mid {
+        return Err(PreservesError::UnexpectedEof);
+    }
+    Ok(bytes.split_at(mid))
+}
+
+/// Rejects an on-wire item/entry count that couldn't possibly fit in the
+/// remaining bytes, so a corrupted or malicious `len` (e.g. `u32::MAX`)
+/// can't make a decoder pre-allocate gigabytes before any real validation.
+/// `min_bytes_per_item` is a lower bound on one item's encoded size: every
+/// value starts with a 1-byte tag, so 1 for a sequence/record field and 2
+/// for a dictionary entry's key and value.
+fn check_count(len: usize, remaining: usize, min_bytes_per_item: usize) -> Result<(), PreservesError> {
+    match len.checked_mul(min_bytes_per_item) {
+        Some(needed) if needed <= remaining => Ok(()),
+        _ => Err(PreservesError::UnexpectedEof),
+    }
+}
+
+/// Encodes a [`Value`] to its tagged binary representation.
+pub fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Integer(i) => {
+            out.push(TAG_INTEGER);
+            out.extend_from_slice(&i.to_le_bytes());
+        }
+        Value::Bytes(bytes) => {
+            out.push(TAG_BYTES);
+            write_len(out, bytes.len());
+            out.extend_from_slice(bytes);
+        }
+        Value::String(s) => {
+            out.push(TAG_STRING);
+            write_len(out, s.len());
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Sequence(items) => {
+            out.push(TAG_SEQUENCE);
+            write_len(out, items.len());
+            for item in items {
+                encode_value(item, out);
+            }
+        }
+        Value::Dictionary(entries) => {
+            out.push(TAG_DICTIONARY);
+            write_len(out, entries.len());
+            for (key, value) in entries {
+                encode_value(key, out);
+                encode_value(value, out);
+            }
+        }
+        Value::Record { label, fields } => {
+            out.push(TAG_RECORD);
+            write_len(out, label.len());
+            out.extend_from_slice(label.as_bytes());
+            write_len(out, fields.len());
+            for field in fields {
+                encode_value(field, out);
+            }
+        }
+    }
+}
+
+/// Decodes a single [`Value`] from the front of `bytes`, returning the
+/// unconsumed remainder.
+pub fn decode_value(bytes: &[u8]) -> Result<(Value, &[u8]), PreservesError> {
+    let (&tag, rest) = bytes.split_first().ok_or(PreservesError::UnexpectedEof)?;
+
+    match tag {
+        TAG_INTEGER => {
+            let (int_bytes, rest) = split_at(rest, 8)?;
+            let value = i64::from_le_bytes(int_bytes.try_into().unwrap());
+            Ok((Value::Integer(value), rest))
+        }
+        TAG_BYTES => {
+            let (len, rest) = read_len(rest)?;
+            let (bytes, rest) = split_at(rest, len)?;
+            Ok((Value::Bytes(bytes.to_vec()), rest))
+        }
+        TAG_STRING => {
+            let (len, rest) = read_len(rest)?;
+            let (bytes, rest) = split_at(rest, len)?;
+            Ok((Value::String(String::from_utf8(bytes.to_vec())?), rest))
+        }
+        TAG_SEQUENCE => {
+            let (len, mut rest) = read_len(rest)?;
+            check_count(len, rest.len(), 1)?;
+            let mut items = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (item, remainder) = decode_value(rest)?;
+                items.push(item);
+                rest = remainder;
+            }
+            Ok((Value::Sequence(items), rest))
+        }
+        TAG_DICTIONARY => {
+            let (len, mut rest) = read_len(rest)?;
+            check_count(len, rest.len(), 2)?;
+            let mut entries = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (key, remainder) = decode_value(rest)?;
+                let (value, remainder) = decode_value(remainder)?;
+                entries.push((key, value));
+                rest = remainder;
+            }
+            Ok((Value::Dictionary(entries), rest))
+        }
+        TAG_RECORD => {
+            let (label_len, rest) = read_len(rest)?;
+            let (label_bytes, rest) = split_at(rest, label_len)?;
+            let label = String::from_utf8(label_bytes.to_vec())?;
+
+            let (len, mut rest) = read_len(rest)?;
+            check_count(len, rest.len(), 1)?;
+            let mut fields = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (field, remainder) = decode_value(rest)?;
+                fields.push(field);
+                rest = remainder;
+            }
+            Ok((Value::Record { label, fields }, rest))
+        }
+        other => Err(PreservesError::UnknownTag(other)),
+    }
+}
+
+/// Converts `value` into the Preserves [`Value`] tree `serde` would produce
+/// for it.
+pub fn to_value<T: Serialize>(value: &T) -> Result<Value, PreservesError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Reconstructs a `T` from a Preserves [`Value`] tree.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, PreservesError> {
+    T::deserialize(value)
+}
+
+struct ValueSerializer;
+
+struct SequenceSerializer {
+    items: Vec<Value>,
+}
+
+struct RecordSerializer {
+    label: String,
+    fields: Vec<Value>,
+}
+
+struct DictionarySerializer {
+    entries: Vec<(Value, Value)>,
+    next_key: Option<Value>,
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    type SerializeSeq = SequenceSerializer;
+    type SerializeTuple = SequenceSerializer;
+    type SerializeTupleStruct = RecordSerializer;
+    type SerializeTupleVariant = RecordSerializer;
+    type SerializeMap = DictionarySerializer;
+    type SerializeStruct = RecordSerializer;
+    type SerializeStructVariant = RecordSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value, Self::Error> {
+        Ok(Value::Integer(v as i64))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value, Self::Error> {
+        i64::try_from(v).map(Value::Integer).map_err(|_| {
+            PreservesError::Unrepresentable("u64 value exceeds i64::MAX")
+        })
+    }
+    fn serialize_f32(self, _v: f32) -> Result<Value, Self::Error> {
+        Err(PreservesError::Unrepresentable("floating point"))
+    }
+    fn serialize_f64(self, _v: f64) -> Result<Value, Self::Error> {
+        Err(PreservesError::Unrepresentable("floating point"))
+    }
+    fn serialize_char(self, v: char) -> Result<Value, Self::Error> {
+        Ok(Value::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value, Self::Error> {
+        Ok(Value::String(v.to_owned()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Self::Error> {
+        Ok(Value::Bytes(v.to_owned()))
+    }
+    fn serialize_none(self) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: "none".to_owned(),
+            fields: Vec::new(),
+        })
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: "some".to_owned(),
+            fields: vec![value.serialize(ValueSerializer)?],
+        })
+    }
+    fn serialize_unit(self) -> Result<Value, Self::Error> {
+        Ok(Value::Sequence(Vec::new()))
+    }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: name.to_owned(),
+            fields: Vec::new(),
+        })
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: variant.to_owned(),
+            fields: Vec::new(),
+        })
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: name.to_owned(),
+            fields: vec![value.serialize(ValueSerializer)?],
+        })
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: variant.to_owned(),
+            fields: vec![value.serialize(ValueSerializer)?],
+        })
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SequenceSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(SequenceSerializer {
+            items: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(RecordSerializer {
+            label: name.to_owned(),
+            fields: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(RecordSerializer {
+            label: variant.to_owned(),
+            fields: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(DictionarySerializer {
+            entries: Vec::new(),
+            next_key: None,
+        })
+    }
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(RecordSerializer {
+            label: name.to_owned(),
+            fields: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(RecordSerializer {
+            label: variant.to_owned(),
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+impl SerializeSeq for SequenceSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Sequence(self.items))
+    }
+}
+
+impl SerializeTuple for SequenceSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Sequence(self.items))
+    }
+}
+
+impl SerializeTupleStruct for RecordSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.fields.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: self.label,
+            fields: self.fields,
+        })
+    }
+}
+
+impl SerializeTupleVariant for RecordSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.fields.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: self.label,
+            fields: self.fields,
+        })
+    }
+}
+
+impl SerializeStruct for RecordSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.fields.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: self.label,
+            fields: self.fields,
+        })
+    }
+}
+
+impl SerializeStructVariant for RecordSerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.fields.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Record {
+            label: self.label,
+            fields: self.fields,
+        })
+    }
+}
+
+impl SerializeMap for DictionarySerializer {
+    type Ok = Value;
+    type Error = PreservesError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.next_key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        Ok(Value::Dictionary(self.entries))
+    }
+}
+
+struct SequenceAccess {
+    items: std::vec::IntoIter<Value>,
+}
+
+impl<'de> SeqAccess<'de> for SequenceAccess {
+    type Error = PreservesError;
+
+    fn next_element_seed<T: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.items.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct DictionaryAccess {
+    entries: std::vec::IntoIter<(Value, Value)>,
+    pending_value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for DictionaryAccess {
+    type Error = PreservesError;
+
+    fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.pending_value = Some(value);
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .pending_value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(value)
+    }
+}
+
+struct RecordEnumAccess {
+    label: String,
+    fields: Vec<Value>,
+}
+
+impl<'de> EnumAccess<'de> for RecordEnumAccess {
+    type Error = PreservesError;
+    type Variant = RecordVariantAccess;
+
+    fn variant_seed<V: serde::de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(Value::String(self.label))?;
+        Ok((variant, RecordVariantAccess(self.fields)))
+    }
+}
+
+struct RecordVariantAccess(Vec<Value>);
+
+impl<'de> VariantAccess<'de> for RecordVariantAccess {
+    type Error = PreservesError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: serde::de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let mut fields = self.0;
+        let value = fields
+            .pop()
+            .ok_or(PreservesError::UnexpectedShape("missing newtype field"))?;
+        seed.deserialize(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SequenceAccess {
+            items: self.0.into_iter(),
+        })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SequenceAccess {
+            items: self.0.into_iter(),
+        })
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = PreservesError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Integer(i) => visitor.visit_i64(i),
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Sequence(items) => visitor.visit_seq(SequenceAccess {
+                items: items.into_iter(),
+            }),
+            Value::Dictionary(entries) => visitor.visit_map(DictionaryAccess {
+                entries: entries.into_iter(),
+                pending_value: None,
+            }),
+            Value::Record { fields, .. } => visitor.visit_seq(SequenceAccess {
+                items: fields.into_iter(),
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Record { label, mut fields } if label == "none" && fields.is_empty() => {
+                visitor.visit_none()
+            }
+            Value::Record { label, mut fields } if label == "some" && fields.len() == 1 => {
+                visitor.visit_some(fields.remove(0))
+            }
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Integer(i @ (0 | 1)) => visitor.visit_bool(i == 1),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Sequence(items) if items.is_empty() => visitor.visit_unit(),
+            Value::Record { fields, .. } if fields.is_empty() => visitor.visit_unit(),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Value::Record { label, fields } => {
+                visitor.visit_enum(RecordEnumAccess { label, fields })
+            }
+            Value::String(label) => visitor.visit_enum(RecordEnumAccess {
+                label,
+                fields: Vec::new(),
+            }),
+            _ => Err(PreservesError::UnexpectedShape(
+                "expected a record or string for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// A self-describing binary codec modeled on Preserves.
+///
+/// Messages encoded with this codec carry their own structure, so they can
+/// be round-tripped or inspected without the receiver knowing the concrete
+/// Rust type in advance.
+///
+/// Caution: the Preserves data model has no floating-point representation,
+/// and its integers are signed 64-bit, so `T` must not contain an `f32`,
+/// `f64`, or a `u64` greater than `i64::MAX` — [`Codec::encode`] panics on
+/// such a value, since `Codec`'s signature can't return an error. Use
+/// [`PreservesCodec::try_encode`] to validate a value before publishing it.
+pub struct PreservesCodec<T>(PhantomData<T>);
+
+impl<T> PreservesCodec<T> {
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for PreservesCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for PreservesCodec<T> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Copy for PreservesCodec<T> {}
+
+impl<T: Serialize> PreservesCodec<T> {
+    /// Encodes `value`, returning an error instead of panicking if `T`
+    /// contains data the Preserves data model can't represent (a float, or
+    /// a `u64` above `i64::MAX`). Callers that can't guarantee `T` is
+    /// always representable should validate with this before calling
+    /// [`Codec::encode`], which panics on the same inputs.
+    pub fn try_encode(&self, value: &T) -> Result<Vec<u8>, PreservesError> {
+        let value = to_value(value)?;
+        let mut out = Vec::new();
+        encode_value(&value, &mut out);
+        Ok(out)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for PreservesCodec<T> {
+    type Error = PreservesError;
+
+    const CONTENT_TYPE: &'static str = "application/preserves";
+
+    fn encode(&self, value: &T) -> Vec<u8> {
+        self.try_encode(value).expect(
+            "T must be representable in the Preserves data model (no f32/f64, no u64 > i64::MAX — validate with PreservesCodec::try_encode first)",
+        )
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<T, Self::Error> {
+        let (value, rest) = decode_value(bytes)?;
+        if !rest.is_empty() {
+            return Err(PreservesError::TrailingBytes);
+        }
+        from_value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{decode_value, encode_value, PreservesCodec, PreservesError, Value};
+    use crate::Codec;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug>(
+        value: T,
+    ) {
+        let codec = PreservesCodec::<T>::new();
+        let bytes = codec.try_encode(&value).unwrap();
+        let decoded: T = codec.decode(&bytes).unwrap();
+        assert_eq!(value, decoded);
+    }
+
+    #[test]
+    fn value_round_trips_through_the_binary_encoding() {
+        let value = Value::Sequence(vec![
+            Value::Integer(-7),
+            Value::String("hi".to_owned()),
+            Value::Bytes(vec![1, 2, 3]),
+            Value::Dictionary(vec![(Value::String("k".to_owned()), Value::Integer(1))]),
+            Value::Record {
+                label: "point".to_owned(),
+                fields: vec![Value::Integer(1), Value::Integer(2)],
+            },
+        ]);
+
+        let mut bytes = Vec::new();
+        encode_value(&value, &mut bytes);
+        let (decoded, rest) = decode_value(&bytes).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(value, decoded);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[test]
+    fn struct_round_trips() {
+        round_trip(Point { x: 1, y: -2 });
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle(u32),
+        Rectangle { width: u32, height: u32 },
+        Empty,
+    }
+
+    #[test]
+    fn enum_round_trips() {
+        round_trip(Shape::Circle(3));
+        round_trip(Shape::Rectangle {
+            width: 4,
+            height: 5,
+        });
+        round_trip(Shape::Empty);
+    }
+
+    #[test]
+    fn option_round_trips() {
+        round_trip(Some(42u32));
+        round_trip(None::<u32>);
+    }
+
+    #[test]
+    fn vec_and_map_round_trip() {
+        round_trip(vec![1, 2, 3]);
+
+        let mut map = HashMap::new();
+        map.insert("a".to_owned(), 1);
+        map.insert("b".to_owned(), 2);
+        round_trip(map);
+    }
+
+    #[test]
+    fn u64_above_i64_max_is_rejected_not_reinterpreted() {
+        let codec = PreservesCodec::<u64>::new();
+        let err = codec.try_encode(&(i64::MAX as u64 + 1)).unwrap_err();
+        assert!(matches!(err, PreservesError::Unrepresentable(_)));
+    }
+
+    #[test]
+    fn floats_are_rejected() {
+        let codec = PreservesCodec::<f64>::new();
+        let err = codec.try_encode(&1.5).unwrap_err();
+        assert!(matches!(err, PreservesError::Unrepresentable(_)));
+    }
+
+    #[test]
+    fn truncated_count_is_rejected_instead_of_over_allocating() {
+        // TAG_SEQUENCE followed by a claimed length of u32::MAX with no
+        // element bytes behind it.
+        let mut bytes = vec![super::TAG_SEQUENCE];
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let err = decode_value(&bytes).unwrap_err();
+        assert!(matches!(err, PreservesError::UnexpectedEof));
+    }
+}
@@ -1,13 +1,23 @@
+use std::future::Future;
 use std::pin::Pin;
 
 use futures_lite::{Stream, StreamExt};
 use lapin::{
-    options::{BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, QueueDeclareOptions},
-    types::FieldTable,
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions,
+        QueueBindOptions, QueueDeclareOptions,
+    },
+    types::{AMQPValue, FieldTable, ShortString},
     BasicProperties, Channel as LapinChannel, Consumer as LapinLibConsumer, Queue as LapinQueue,
 };
 
-use crate::{AmqpConsumerError, AmqpQueueDeclaration, AmqpQueueInformation, Consumer, Producer};
+use crate::{
+    AckStrategy, AmqpConsumerError, AmqpQueueDeclaration, AmqpQueueInformation, Codec, Consumer,
+    Producer,
+};
+
+const RETRY_COUNT_HEADER: &str = "x-retry-count";
 
 // Extend the AmqpQueueDeclaration struct with lapin-related methods
 impl<'a> AmqpQueueInformation<'a> {
@@ -30,27 +40,41 @@ impl<'a> AmqpQueueInformation<'a> {
         &self,
         channel: &LapinChannel,
     ) -> Result<LapinQueue, lapin::Error> {
-        channel
+        let queue = channel
             .queue_declare(
                 self.queue_name,
                 QueueDeclareOptions::default(),
                 FieldTable::default(),
             )
-            .await
+            .await?;
+
+        for pattern in self.binding_patterns {
+            channel
+                .queue_bind(
+                    self.queue_name,
+                    self.exchange,
+                    pattern,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        Ok(queue)
     }
 }
 
-pub struct LapinProducer<'a, 'c, T, S: Fn(T) -> Vec<u8>> {
+pub struct LapinProducer<'a, 'c, T, C: Codec<T>> {
     channel: &'c LapinChannel,
     queue_information: AmqpQueueInformation<'a>,
-    serializer: S,
+    codec: C,
     marker: std::marker::PhantomData<T>,
 }
-impl<'a, 'c, T: Send + Sync> LapinProducer<'a, 'c, T, fn(T) -> Vec<u8>> {
-    pub async fn new<DError>(
+impl<'a, 'c, T: Send + Sync, C: Codec<T>> LapinProducer<'a, 'c, T, C> {
+    pub async fn new(
         channel: &'c LapinChannel,
-        queue_declaration: AmqpQueueDeclaration<'a, T, DError>,
-    ) -> Result<LapinProducer<'a, 'c, T, fn(T) -> Vec<u8>>, lapin::Error> {
+        queue_declaration: AmqpQueueDeclaration<'a, T, C>,
+    ) -> Result<LapinProducer<'a, 'c, T, C>, lapin::Error> {
         queue_declaration
             .information
             .declare_lapin_queue(channel)
@@ -59,20 +83,18 @@ impl<'a, 'c, T: Send + Sync> LapinProducer<'a, 'c, T, fn(T) -> Vec<u8>> {
         Ok(Self {
             channel,
             queue_information: queue_declaration.information,
-            serializer: queue_declaration.serializer,
+            codec: queue_declaration.codec,
             marker: std::marker::PhantomData,
         })
     }
 }
 
 #[async_trait::async_trait]
-impl<'a, 'c, T: Send + Sync, S: (Fn(T) -> Vec<u8>) + Send + Sync> Producer<T>
-    for LapinProducer<'a, 'c, T, S>
-{
+impl<'a, 'c, T: Send + Sync, C: Codec<T> + Send + Sync> Producer<T> for LapinProducer<'a, 'c, T, C> {
     type Error = lapin::Error;
 
     async fn publish(&self, value: T) -> Result<(), Self::Error> {
-        let payload = (self.serializer)(value);
+        let payload = self.codec.encode(&value);
 
         let _confirm = self
             .channel
@@ -81,7 +103,9 @@ impl<'a, 'c, T: Send + Sync, S: (Fn(T) -> Vec<u8>) + Send + Sync> Producer<T>
                 self.queue_information.routing_key,
                 BasicPublishOptions::default(),
                 payload.as_slice(),
-                BasicProperties::default().with_delivery_mode(2),
+                BasicProperties::default()
+                    .with_delivery_mode(2)
+                    .with_content_type(C::CONTENT_TYPE.into()),
             )
             .await?
             .await?;
@@ -90,17 +114,19 @@ impl<'a, 'c, T: Send + Sync, S: (Fn(T) -> Vec<u8>) + Send + Sync> Producer<T>
     }
 }
 
-pub struct LapinConsumer<T, DError, D: Fn(Vec<u8>) -> Result<T, DError>> {
+pub struct LapinConsumer<T, C: Codec<T>> {
     consumer: LapinLibConsumer,
-    deserializer: D,
+    codec: C,
+    channel: LapinChannel,
+    ack_strategy: AckStrategy,
 }
 
-impl<'a, T, DError> LapinConsumer<T, DError, fn(Vec<u8>) -> Result<T, DError>> {
+impl<'a, T, C: Codec<T>> LapinConsumer<T, C> {
     pub async fn new(
         channel: &LapinChannel,
-        queue_declaration: AmqpQueueDeclaration<'a, T, DError>,
+        queue_declaration: AmqpQueueDeclaration<'a, T, C>,
         consumer_tag: &str,
-    ) -> Result<LapinConsumer<T, DError, fn(Vec<u8>) -> Result<T, DError>>, lapin::Error> {
+    ) -> Result<LapinConsumer<T, C>, lapin::Error> {
         let consumer = queue_declaration
             .information
             .create_lapin_consumer(channel, consumer_tag)
@@ -108,25 +134,148 @@ impl<'a, T, DError> LapinConsumer<T, DError, fn(Vec<u8>) -> Result<T, DError>> {
 
         Ok(Self {
             consumer,
-            deserializer: queue_declaration.deserializer,
+            codec: queue_declaration.codec,
+            channel: channel.clone(),
+            ack_strategy: queue_declaration.ack_strategy,
         })
     }
 }
 
-impl<
-        'a,
-        T: Send + Sync,
-        DError: Send + Sync,
-        D: Fn(Vec<u8>) -> Result<T, DError> + Send + Sync,
-    > Consumer<'a, T, DError> for LapinConsumer<T, DError, D>
+/// Reads the current retry count out of `headers` (0 if absent), and
+/// returns it alongside a copy of `headers` with the count incremented by
+/// one, ready to publish the retried or dead-lettered message with. The
+/// returned count is the number of retries already consumed *before* this
+/// attempt, so `max_retries = 0` reports `0` retries when it dead-letters
+/// on the very first failure, not `1`.
+fn bump_retry_count(headers: &Option<FieldTable>) -> (u32, FieldTable) {
+    let retry_count = headers
+        .as_ref()
+        .and_then(|headers| headers.inner().get(&ShortString::from(RETRY_COUNT_HEADER)))
+        .and_then(|value| match value {
+            AMQPValue::LongLongInt(count) => Some(*count as u32),
+            _ => None,
+        })
+        .unwrap_or(0);
+
+    let mut next_headers = headers.clone().unwrap_or_default();
+    next_headers.insert(
+        RETRY_COUNT_HEADER.into(),
+        AMQPValue::LongLongInt((retry_count + 1) as i64),
+    );
+
+    (retry_count, next_headers)
+}
+
+/// Resolves a delivery that failed — whether because it failed to decode or
+/// because whoever received it reported it couldn't be handled — according
+/// to `ack_strategy`. Returns `Ok(Some(attempts))` once the message has been
+/// routed to the dead-letter queue after exhausting its retries, `Ok(None)`
+/// otherwise.
+async fn resolve_failure(
+    channel: &LapinChannel,
+    ack_strategy: &AckStrategy,
+    delivery: &Delivery,
+) -> Result<Option<u32>, lapin::Error> {
+    match ack_strategy {
+        // Already acked on receipt; nothing left to do.
+        AckStrategy::Immediate => Ok(None),
+        AckStrategy::AckOnSuccess => {
+            delivery
+                .nack(BasicNackOptions {
+                    requeue: true,
+                    ..BasicNackOptions::default()
+                })
+                .await?;
+            Ok(None)
+        }
+        AckStrategy::DeadLetter {
+            max_retries,
+            retry_exchange,
+            retry_routing_key,
+            dead_letter_exchange,
+            dead_letter_routing_key,
+        } => {
+            let (retry_count, headers) = bump_retry_count(delivery.properties.headers());
+            let properties = delivery.properties.clone().with_headers(headers);
+
+            if retry_count < *max_retries {
+                channel
+                    .basic_publish(
+                        retry_exchange,
+                        retry_routing_key,
+                        BasicPublishOptions::default(),
+                        &delivery.data,
+                        properties,
+                    )
+                    .await?
+                    .await?;
+                delivery.ack(BasicAckOptions::default()).await?;
+                Ok(None)
+            } else {
+                channel
+                    .basic_publish(
+                        dead_letter_exchange,
+                        dead_letter_routing_key,
+                        BasicPublishOptions::default(),
+                        &delivery.data,
+                        properties,
+                    )
+                    .await?
+                    .await?;
+                delivery.ack(BasicAckOptions::default()).await?;
+                Ok(Some(retry_count))
+            }
+        }
+    }
+}
+
+/// A [`Delivery`] that decoded successfully, not yet acked. Yielded by
+/// [`LapinConsumer::to_stream`] alongside the decoded value; the caller
+/// acks it once it has actually handled the value, or fails it to apply
+/// the queue's `AckStrategy` as if decoding itself had failed.
+pub struct LapinDeliveryHandle {
+    delivery: Delivery,
+    channel: LapinChannel,
+    ack_strategy: AckStrategy,
+    /// Set when `AckStrategy::Immediate` already acked this delivery on
+    /// receipt, before it was even decoded — `ack`/`fail` become no-ops.
+    already_resolved: bool,
+}
+
+#[async_trait::async_trait]
+impl crate::DeliveryHandle for LapinDeliveryHandle {
+    type Error = lapin::Error;
+
+    async fn ack(self) -> Result<(), Self::Error> {
+        if self.already_resolved {
+            return Ok(());
+        }
+        self.delivery.ack(BasicAckOptions::default()).await
+    }
+
+    async fn fail(self) -> Result<Option<u32>, Self::Error> {
+        if self.already_resolved {
+            return Ok(None);
+        }
+        resolve_failure(&self.channel, &self.ack_strategy, &self.delivery).await
+    }
+}
+
+impl<'a, T: Send + Sync, C: Codec<T> + Send + Sync> Consumer<'a, T, C::Error>
+    for LapinConsumer<T, C>
 where
     Self: 'a,
+    C::Error: Send + Sync,
 {
     type Error = lapin::Error;
+    type DeliveryHandle = LapinDeliveryHandle;
     type Stream = Pin<
         Box<
-            dyn Stream<Item = Option<Result<T, AmqpConsumerError<Self::Error, DError>>>>
-                + Send
+            dyn Stream<
+                    Item = Option<
+                        Result<(T, LapinDeliveryHandle), AmqpConsumerError<Self::Error, C::Error>>,
+                    >,
+                > + Send
                 + 'a,
         >,
     >;
@@ -151,26 +300,309 @@ where
                 }
               };
 
-              match delivery.ack(BasicAckOptions::default()).await {
-                Ok(()) => (),
-                Err(err) => {
+              // `Immediate` acks on receipt, before decoding, matching the
+              // original at-most-once behavior. Every other strategy defers
+              // acking to the handle yielded below, which the caller
+              // resolves only once it has actually handled the value.
+              let already_resolved = matches!(self.ack_strategy, AckStrategy::Immediate);
+              if already_resolved {
+                if let Err(err) = delivery.ack(BasicAckOptions::default()).await {
                   yield Some(Err(AmqpConsumerError::ConsumerError(err)));
                   continue;
                 }
-              };
+              }
+
+              let content_type = delivery.properties.content_type().as_ref().map(|s| s.to_string());
+              if content_type.as_deref() != Some(C::CONTENT_TYPE) {
+                match resolve_failure(&self.channel, &self.ack_strategy, &delivery).await {
+                  Ok(Some(attempts)) => yield Some(Err(AmqpConsumerError::DeadLettered { attempts })),
+                  Ok(None) => yield Some(Err(AmqpConsumerError::ContentTypeMismatch {
+                    expected: C::CONTENT_TYPE,
+                    actual: content_type,
+                  })),
+                  Err(err) => yield Some(Err(AmqpConsumerError::ConsumerError(err))),
+                }
+                continue;
+              }
 
-              let value = match (self.deserializer)(delivery.data) {
+              let value = match self.codec.decode(&delivery.data) {
                 Ok(value) => value,
                 Err(err) => {
-
-                  yield Some(Err(AmqpConsumerError::DeserializationError(err)));
+                  match resolve_failure(&self.channel, &self.ack_strategy, &delivery).await {
+                    Ok(Some(attempts)) => yield Some(Err(AmqpConsumerError::DeadLettered { attempts })),
+                    Ok(None) => yield Some(Err(AmqpConsumerError::DeserializationError(err))),
+                    Err(consumer_err) => yield Some(Err(AmqpConsumerError::ConsumerError(consumer_err))),
+                  }
                   continue;
-
                 }
               };
 
-              yield Some(Ok(value));
+              let handle = LapinDeliveryHandle {
+                delivery,
+                channel: self.channel.clone(),
+                ack_strategy: self.ack_strategy.clone(),
+                already_resolved,
+              };
+
+              yield Some(Ok((value, handle)));
           }
         })
     }
 }
+
+/// `true` once the handler has run and the delivery should be acked;
+/// `false` if it failed to decode and should instead be nacked.
+type BoxedHandlerFuture = Pin<Box<dyn Future<Output = bool> + Send>>;
+type BoxedHandler = Box<dyn Fn(Vec<u8>) -> BoxedHandlerFuture + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternSegment {
+    Literal(String),
+    /// `*` — matches exactly one routing-key segment.
+    Star,
+    /// `#` — matches zero or more routing-key segments, at the end of the
+    /// pattern or in the middle of it.
+    Hash,
+}
+
+fn parse_pattern(pattern: &str) -> Vec<PatternSegment> {
+    pattern
+        .split('.')
+        .map(|segment| match segment {
+            "*" => PatternSegment::Star,
+            "#" => PatternSegment::Hash,
+            literal => PatternSegment::Literal(literal.to_owned()),
+        })
+        .collect()
+}
+
+fn matches_pattern(pattern: &[PatternSegment], segments: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((PatternSegment::Hash, rest)) => {
+            (0..=segments.len()).any(|skip| matches_pattern(rest, &segments[skip..]))
+        }
+        Some((PatternSegment::Star, rest)) => {
+            !segments.is_empty() && matches_pattern(rest, &segments[1..])
+        }
+        Some((PatternSegment::Literal(literal), rest)) => segments
+            .split_first()
+            .is_some_and(|(head, tail)| head == literal && matches_pattern(rest, tail)),
+    }
+}
+
+/// Dataspace-style dispatch: binds a queue to a topic exchange under one
+/// pattern per registered handler, then routes each delivery to the first
+/// handler whose pattern matches the delivery's routing key.
+///
+/// Patterns follow AMQP topic-exchange rules: `*` matches exactly one
+/// routing-key segment, `#` matches zero or more segments.
+/// Deliveries that match no pattern are nacked without requeue, so a
+/// dead-letter exchange configured on the queue picks them up.
+pub struct Dispatcher {
+    routes: Vec<(Vec<PatternSegment>, BoxedHandler)>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    /// Registers `handler` for deliveries whose routing key matches
+    /// `pattern`: the delivery's body is decoded via `codec` before
+    /// `handler` is invoked with the decoded value. Patterns are tried in
+    /// registration order; the first match wins. A delivery whose body
+    /// fails to decode is nacked without requeue instead of reaching
+    /// `handler`.
+    pub fn on<T, C, F, Fut>(mut self, pattern: &str, codec: C, handler: F) -> Self
+    where
+        C: Codec<T> + Send + Sync + 'static,
+        T: Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.routes.push((
+            parse_pattern(pattern),
+            Box::new(move |payload: Vec<u8>| {
+                let value = codec.decode(&payload);
+                let handled = handler(match value {
+                    Ok(value) => value,
+                    Err(_) => return Box::pin(async { false }) as BoxedHandlerFuture,
+                });
+                Box::pin(async move {
+                    handled.await;
+                    true
+                }) as BoxedHandlerFuture
+            }),
+        ));
+        self
+    }
+
+    fn handler_for(&self, routing_key: &str) -> Option<&BoxedHandler> {
+        let segments: Vec<&str> = routing_key.split('.').collect();
+        self.routes
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, &segments))
+            .map(|(_, handler)| handler)
+    }
+
+    /// Binds `queue_information` to its exchange under every registered
+    /// pattern, then consumes from it until the broker closes the
+    /// consumer.
+    pub async fn run(
+        &self,
+        channel: &LapinChannel,
+        queue_information: AmqpQueueInformation<'_>,
+        consumer_tag: &str,
+    ) -> Result<(), lapin::Error> {
+        queue_information.declare_lapin_queue(channel).await?;
+
+        for (pattern, _) in &self.routes {
+            let pattern = pattern
+                .iter()
+                .map(|segment| match segment {
+                    PatternSegment::Literal(literal) => literal.clone(),
+                    PatternSegment::Star => "*".to_owned(),
+                    PatternSegment::Hash => "#".to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join(".");
+
+            channel
+                .queue_bind(
+                    queue_information.queue_name,
+                    queue_information.exchange,
+                    &pattern,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        let mut consumer = queue_information
+            .create_lapin_consumer(channel, consumer_tag)
+            .await?;
+
+        while let Some(delivery) = consumer.next().await {
+            let delivery = delivery?;
+            let routing_key = delivery.routing_key.to_string();
+
+            match self.handler_for(&routing_key) {
+                Some(handler) => {
+                    if handler(delivery.data.clone()).await {
+                        delivery.ack(BasicAckOptions::default()).await?;
+                    } else {
+                        delivery
+                            .nack(BasicNackOptions {
+                                requeue: false,
+                                ..BasicNackOptions::default()
+                            })
+                            .await?;
+                    }
+                }
+                None => {
+                    delivery
+                        .nack(BasicNackOptions {
+                            requeue: false,
+                            ..BasicNackOptions::default()
+                        })
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod retry_count_tests {
+    use super::bump_retry_count;
+    use lapin::types::{AMQPValue, FieldTable, ShortString};
+
+    use super::RETRY_COUNT_HEADER;
+
+    #[test]
+    fn missing_header_starts_at_zero_and_bumps_to_one() {
+        let (retry_count, headers) = bump_retry_count(&None);
+
+        assert_eq!(retry_count, 0);
+        assert_eq!(
+            headers
+                .inner()
+                .get(&ShortString::from(RETRY_COUNT_HEADER)),
+            Some(&AMQPValue::LongLongInt(1))
+        );
+    }
+
+    #[test]
+    fn existing_header_is_reported_as_is_and_bumped_by_one() {
+        let mut existing = FieldTable::default();
+        existing.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongLongInt(3));
+
+        let (retry_count, headers) = bump_retry_count(&Some(existing));
+
+        assert_eq!(retry_count, 3);
+        assert_eq!(
+            headers
+                .inner()
+                .get(&ShortString::from(RETRY_COUNT_HEADER)),
+            Some(&AMQPValue::LongLongInt(4))
+        );
+    }
+}
+
+#[cfg(test)]
+mod pattern_tests {
+    use super::{matches_pattern, parse_pattern};
+
+    fn matches(pattern: &str, routing_key: &str) -> bool {
+        let segments: Vec<&str> = routing_key.split('.').collect();
+        matches_pattern(&parse_pattern(pattern), &segments)
+    }
+
+    #[test]
+    fn literal_segments_must_match_exactly() {
+        assert!(matches("a.b.c", "a.b.c"));
+        assert!(!matches("a.b.c", "a.b.d"));
+        assert!(!matches("a.b.c", "a.b"));
+        assert!(!matches("a.b.c", "a.b.c.d"));
+    }
+
+    #[test]
+    fn star_matches_exactly_one_segment() {
+        assert!(matches("a.*.c", "a.b.c"));
+        assert!(!matches("a.*.c", "a.c"));
+        assert!(!matches("a.*.c", "a.b.b.c"));
+    }
+
+    #[test]
+    fn hash_matches_zero_or_more_trailing_segments() {
+        assert!(matches("a.#", "a"));
+        assert!(matches("a.#", "a.b"));
+        assert!(matches("a.#", "a.b.c"));
+        assert!(!matches("a.#", "b"));
+    }
+
+    #[test]
+    fn hash_can_match_in_the_middle_of_a_pattern() {
+        assert!(matches("a.#.z", "a.z"));
+        assert!(matches("a.#.z", "a.b.z"));
+        assert!(matches("a.#.z", "a.b.c.z"));
+        assert!(!matches("a.#.z", "a.b.c"));
+    }
+
+    #[test]
+    fn bare_star_and_hash_match_the_whole_key() {
+        assert!(matches("#", ""));
+        assert!(matches("#", "a.b.c"));
+        assert!(matches("*", "a"));
+        assert!(!matches("*", "a.b"));
+    }
+}
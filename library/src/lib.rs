@@ -7,12 +7,81 @@ pub struct AmqpQueueInformation<'a> {
     pub queue_name: &'a str,
     pub exchange: &'a str,
     pub routing_key: &'a str,
+    /// Topic-exchange binding patterns (`*`/`#` wildcards) to bind
+    /// `queue_name` to `exchange` under when the queue is declared.
+    /// `routing_key` itself is not bound; it is only used on publish.
+    /// Empty for no explicit binding.
+    pub binding_patterns: &'a [&'a str],
 }
 
-pub struct AmqpQueueDeclaration<'a, T, DError> {
+/// A wire format for a message body.
+///
+/// `LapinProducer`/`LapinConsumer` use a `Codec` instead of raw
+/// serializer/deserializer function pointers, so the body format (and the
+/// `content_type` advertised on the AMQP message) is chosen by the codec
+/// rather than baked into the queue declaration's type signature.
+pub trait Codec<T> {
+    type Error;
+
+    /// The MIME type set as `content_type` on publish and checked on consume.
+    const CONTENT_TYPE: &'static str;
+
+    fn encode(&self, value: &T) -> Vec<u8>;
+    fn decode(&self, bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// What a `LapinConsumer` does with a delivery depending on how decoding it
+/// turns out, configured per [`AmqpQueueDeclaration`].
+#[derive(Debug, Clone)]
+pub enum AckStrategy {
+    /// Ack immediately on receipt, before decoding — the original
+    /// behavior. A decode failure still permanently consumes the message.
+    Immediate,
+    /// Ack only when the caller acks the decoded delivery's
+    /// [`DeliveryHandle`]; nack with requeue on a decode failure or
+    /// [`DeliveryHandle::fail`].
+    AckOnSuccess,
+    /// On a decode failure or [`DeliveryHandle::fail`], republish the
+    /// message to `retry_exchange`/`retry_routing_key` and ack the original,
+    /// up to `max_retries` times, tracked through the `x-retry-count`
+    /// header; once exhausted, publish to
+    /// `dead_letter_exchange`/`dead_letter_routing_key` instead.
+    DeadLetter {
+        max_retries: u32,
+        retry_exchange: String,
+        retry_routing_key: String,
+        dead_letter_exchange: String,
+        dead_letter_routing_key: String,
+    },
+}
+
+impl Default for AckStrategy {
+    fn default() -> Self {
+        Self::AckOnSuccess
+    }
+}
+
+pub struct AmqpQueueDeclaration<'a, T, C: Codec<T>> {
     pub information: AmqpQueueInformation<'a>,
-    pub serializer: fn(T) -> Vec<u8>,
-    pub deserializer: fn(Vec<u8>) -> Result<T, DError>,
+    pub codec: C,
+    pub ack_strategy: AckStrategy,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<'a, T, C: Codec<T>> AmqpQueueDeclaration<'a, T, C> {
+    pub const fn new(information: AmqpQueueInformation<'a>, codec: C) -> Self {
+        Self {
+            information,
+            codec,
+            ack_strategy: AckStrategy::AckOnSuccess,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    pub fn with_ack_strategy(mut self, ack_strategy: AckStrategy) -> Self {
+        self.ack_strategy = ack_strategy;
+        self
+    }
 }
 
 #[derive(Debug, Error)]
@@ -21,11 +90,27 @@ pub enum AmqpConsumerError<CError, DError> {
     ConsumerError(CError),
     #[error("Deserialization error: {0}")]
     DeserializationError(DError),
+    #[error("unexpected content type: expected `{expected}`, got `{actual:?}`")]
+    ContentTypeMismatch {
+        expected: &'static str,
+        actual: Option<String>,
+    },
+    #[error("message exhausted {attempts} retries and was routed to the dead-letter queue")]
+    DeadLettered { attempts: u32 },
 }
 
+pub mod codec;
+pub mod relay;
+
 #[cfg(feature = "lapin")]
 pub mod lapin;
 
+#[cfg(feature = "lapin")]
+pub mod rpc;
+
+#[cfg(feature = "lapin")]
+pub use rpc::{RPCHandle, RPCHandler};
+
 #[async_trait]
 pub trait Producer<T> {
     type Error;
@@ -33,9 +118,35 @@ pub trait Producer<T> {
     async fn publish(&self, value: T) -> Result<(), Self::Error>;
 }
 
+/// A handle to a single not-yet-resolved delivery, yielded alongside its
+/// decoded value by [`Consumer::to_stream`].
+///
+/// A `Consumer` does not ack a delivery on the caller's behalf just because
+/// it decoded successfully — decoding only proves the bytes were
+/// well-formed, not that whatever the caller does with the value succeeds.
+/// The caller must call [`ack`](Self::ack) once it has actually finished
+/// handling the value, or [`fail`](Self::fail) to apply the queue's
+/// configured `AckStrategy` as if decoding itself had failed.
+#[async_trait]
+pub trait DeliveryHandle: Send {
+    type Error;
+
+    /// Acknowledges the delivery as successfully handled.
+    async fn ack(self) -> Result<(), Self::Error>;
+
+    /// Reports that the caller failed to handle the delivery; applies the
+    /// queue's configured `AckStrategy` (nack-with-requeue, or
+    /// retry/dead-letter) exactly as a decode failure would. Returns
+    /// `Some(attempts)` once the message has been routed to the
+    /// dead-letter queue after exhausting its retries.
+    async fn fail(self) -> Result<Option<u32>, Self::Error>;
+}
+
 pub trait Consumer<'a, T, DError> {
     type Error;
-    type Stream: Stream<Item = Option<Result<T, AmqpConsumerError<Self::Error, DError>>>> + 'a
+    type DeliveryHandle: DeliveryHandle<Error = Self::Error>;
+    type Stream: Stream<Item = Option<Result<(T, Self::DeliveryHandle), AmqpConsumerError<Self::Error, DError>>>>
+        + 'a
     where
         Self: 'a;
 
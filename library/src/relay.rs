@@ -0,0 +1,126 @@
+//! Bridges a [`Consumer`] to one or more [`Producer`]s, continuously
+//! pulling deliveries from a source queue and republishing them to one or
+//! more destination queues — possibly on different connections, or even
+//! different brokers. Mirrors the external-protocol relay pattern used to
+//! bridge a local message space to a remote peer over a transport.
+
+use futures_lite::StreamExt;
+use thiserror::Error;
+
+use crate::{AmqpConsumerError, Consumer, DeliveryHandle, Producer};
+
+#[derive(Debug, Error)]
+pub enum RelayError<CError, DError, PError> {
+    #[error("source error: {0}")]
+    Source(AmqpConsumerError<CError, DError>),
+    #[error("destination error: {0}")]
+    Destination(PError),
+}
+
+/// Relays messages of type `A` from a single [`Consumer`] to one or more
+/// [`Producer`]s of type `B`, applying `transform` to each decoded value.
+///
+/// A message whose `filter` predicate returns `false` is dropped instead of
+/// being forwarded. `transform` runs once per message and the result is
+/// published to every destination, so a single source can fan out to
+/// several queues (or brokers) at once.
+///
+/// The source delivery is only acked once every destination publish has
+/// succeeded; if a publish fails, the delivery is failed instead (applying
+/// the source queue's `AckStrategy`) so a crash or broker error between
+/// decode and publish does not silently drop the message.
+pub struct Relay<A, B, C, P, F> {
+    source: C,
+    destinations: Vec<P>,
+    transform: F,
+    filter: Option<Box<dyn Fn(&A) -> bool + Send + Sync>>,
+    marker: std::marker::PhantomData<B>,
+}
+
+impl<A, B, C, P, F> Relay<A, B, C, P, F>
+where
+    F: Fn(A) -> B,
+{
+    pub fn new(source: C, destination: P, transform: F) -> Self {
+        Self {
+            source,
+            destinations: vec![destination],
+            transform,
+            filter: None,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Adds another destination that every forwarded message is also
+    /// published to.
+    pub fn add_destination(mut self, destination: P) -> Self {
+        self.destinations.push(destination);
+        self
+    }
+
+    /// Drops messages for which `filter` returns `false` instead of
+    /// forwarding them.
+    pub fn with_filter(mut self, filter: impl Fn(&A) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+}
+
+impl<A, B, C, P, F> Relay<A, B, C, P, F>
+where
+    B: Clone,
+    F: Fn(A) -> B,
+{
+    /// Drives the relay until the source stream ends or yields an error.
+    pub async fn run<'a, DError>(&'a mut self) -> Result<(), RelayError<C::Error, DError, P::Error>>
+    where
+        C: Consumer<'a, A, DError>,
+        P: Producer<B>,
+    {
+        let mut stream = self.source.to_stream();
+
+        while let Some(delivery) = stream.next().await {
+            // `None` means the source consumer has ended.
+            let Some(delivery) = delivery else {
+                return Ok(());
+            };
+
+            let (value, handle) = delivery.map_err(RelayError::Source)?;
+
+            if let Some(filter) = &self.filter {
+                if !filter(&value) {
+                    handle
+                        .ack()
+                        .await
+                        .map_err(|err| RelayError::Source(AmqpConsumerError::ConsumerError(err)))?;
+                    continue;
+                }
+            }
+
+            let transformed = (self.transform)(value);
+
+            let mut publish_result = Ok(());
+            for destination in &self.destinations {
+                if let Err(err) = destination.publish(transformed.clone()).await {
+                    publish_result = Err(err);
+                    break;
+                }
+            }
+
+            match publish_result {
+                Ok(()) => handle
+                    .ack()
+                    .await
+                    .map_err(|err| RelayError::Source(AmqpConsumerError::ConsumerError(err)))?,
+                Err(err) => {
+                    handle.fail().await.map_err(|err| {
+                        RelayError::Source(AmqpConsumerError::ConsumerError(err))
+                    })?;
+                    return Err(RelayError::Destination(err));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
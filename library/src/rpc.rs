@@ -0,0 +1,310 @@
+//! Correlation-ID based RPC: a single reply queue multiplexed across many
+//! concurrent in-flight requests, instead of one request at a time.
+//!
+//! Each [`RPCHandle::send`] generates a unique correlation id, registers a
+//! [`oneshot::Sender`] for it, and publishes with `correlation_id`/
+//! `reply_to` set. A single background task, driven by [`RPCHandle::run`],
+//! reads the reply queue and completes whichever pending request the
+//! delivery's `correlation_id` names. [`RPCHandler`] is the server side:
+//! it echoes the incoming `correlation_id` back on `reply_to` so it can
+//! answer several in-flight `RPCHandle`s without mixing up replies.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_lite::StreamExt;
+use lapin::{
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, QueueBindOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+    BasicProperties, Channel as LapinChannel, Consumer as LapinLibConsumer,
+};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::{codec::JsonCodec, Codec};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum RPCError<DError> {
+    #[error("lapin error: {0}")]
+    Lapin(#[from] lapin::Error),
+    #[error("deserialization error: {0}")]
+    Deserialization(DError),
+    #[error("request timed out waiting for a reply")]
+    Timeout,
+    #[error("reply channel was dropped before completing")]
+    Canceled,
+}
+
+/// The client side of a correlation-ID multiplexed RPC exchange.
+///
+/// A single `RPCHandle` can have many [`RPCHandle::send`] futures in
+/// flight at once; [`RPCHandle::run`] must be polled concurrently (e.g.
+/// via `tokio::select!`/`tokio::join!`) to drive replies back to them.
+pub struct RPCHandle<Req, Resp, CReq = JsonCodec<Req>, CResp = JsonCodec<Resp>>
+where
+    CReq: Codec<Req>,
+    CResp: Codec<Resp>,
+{
+    channel: LapinChannel,
+    exchange: String,
+    routing_key: String,
+    reply_queue: String,
+    request_codec: CReq,
+    response_codec: CResp,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<Result<Resp, CResp::Error>>>>>,
+    next_correlation_id: Arc<AtomicU64>,
+    timeout: Duration,
+}
+
+impl<Req, Resp> RPCHandle<Req, Resp, JsonCodec<Req>, JsonCodec<Resp>> {
+    pub async fn new(
+        exchange: &str,
+        routing_key: &str,
+        reply_queue: &str,
+        channel: &LapinChannel,
+    ) -> Result<Self, lapin::Error> {
+        channel
+            .queue_declare(
+                reply_queue,
+                QueueDeclareOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        Ok(Self {
+            channel: channel.clone(),
+            exchange: exchange.to_owned(),
+            routing_key: routing_key.to_owned(),
+            reply_queue: reply_queue.to_owned(),
+            request_codec: JsonCodec::new(),
+            response_codec: JsonCodec::new(),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            next_correlation_id: Arc::new(AtomicU64::new(0)),
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+}
+
+impl<Req, Resp, CReq, CResp> RPCHandle<Req, Resp, CReq, CResp>
+where
+    CReq: Codec<Req>,
+    CResp: Codec<Resp>,
+{
+    /// Overrides the default 30s wait for a reply. A request that is not
+    /// answered within `timeout` resolves with [`RPCError::Timeout`] and its
+    /// pending-map entry is removed, so a lost reply cannot leak it forever.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Publishes `value` with a fresh correlation id and awaits the
+    /// matching reply, up to `self.timeout`. Safe to call concurrently from
+    /// multiple callers; each call multiplexes over the same reply queue.
+    pub async fn send(&self, value: &Req) -> Result<Resp, RPCError<CResp::Error>> {
+        let correlation_id = self
+            .next_correlation_id
+            .fetch_add(1, Ordering::Relaxed)
+            .to_string();
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(correlation_id.clone(), sender);
+
+        let payload = self.request_codec.encode(value);
+
+        let publish = self
+            .channel
+            .basic_publish(
+                &self.exchange,
+                &self.routing_key,
+                BasicPublishOptions::default(),
+                payload.as_slice(),
+                BasicProperties::default()
+                    .with_delivery_mode(2)
+                    .with_content_type(CReq::CONTENT_TYPE.into())
+                    .with_correlation_id(correlation_id.as_str().into())
+                    .with_reply_to(self.reply_queue.as_str().into()),
+            )
+            .await
+            .and_then(|confirm| confirm.await.map(|_| ()).map_err(Into::into));
+
+        if let Err(err) = publish {
+            self.pending.lock().unwrap().remove(&correlation_id);
+            return Err(RPCError::Lapin(err));
+        }
+
+        match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(result)) => result.map_err(RPCError::Deserialization),
+            Ok(Err(_)) => Err(RPCError::Canceled),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&correlation_id);
+                Err(RPCError::Timeout)
+            }
+        }
+    }
+
+    /// Reads the reply queue and completes whichever pending [`send`](Self::send)
+    /// call its `correlation_id` names. Replies with an unrecognized (e.g.
+    /// already-timed-out) correlation id are logged and dropped.
+    pub async fn run(&self) -> Result<(), RPCError<CResp::Error>> {
+        let mut consumer = self
+            .channel
+            .basic_consume(
+                &self.reply_queue,
+                "",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        while let Some(delivery) = consumer.next().await {
+            let delivery = delivery?;
+            delivery.ack(BasicAckOptions::default()).await?;
+
+            let Some(correlation_id) = delivery.properties.correlation_id().as_ref().map(|id| id.to_string())
+            else {
+                continue;
+            };
+
+            let Some(sender) = self.pending.lock().unwrap().remove(&correlation_id) else {
+                eprintln!("RPC reply for unknown correlation id `{correlation_id}`, dropping");
+                continue;
+            };
+
+            let _ = sender.send(self.response_codec.decode(&delivery.data));
+        }
+
+        Ok(())
+    }
+}
+
+/// The server side of a correlation-ID multiplexed RPC exchange: answers
+/// each request on the `reply_to`/`correlation_id` the caller set, so it
+/// can serve several concurrently in-flight `RPCHandle`s.
+pub struct RPCHandler<Req, Resp, F, CReq = JsonCodec<Req>, CResp = JsonCodec<Resp>>
+where
+    CReq: Codec<Req>,
+    CResp: Codec<Resp>,
+{
+    channel: LapinChannel,
+    consumer: LapinLibConsumer,
+    handler: F,
+    request_codec: CReq,
+    response_codec: CResp,
+    marker: PhantomData<(Req, Resp)>,
+}
+
+impl<Req, Resp, F, Fut, HError> RPCHandler<Req, Resp, F, JsonCodec<Req>, JsonCodec<Resp>>
+where
+    F: Fn(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, HError>>,
+{
+    pub async fn new(
+        exchange: &str,
+        queue: &str,
+        channel: &LapinChannel,
+        handler: F,
+    ) -> Result<Self, lapin::Error> {
+        channel
+            .queue_declare(queue, QueueDeclareOptions::default(), FieldTable::default())
+            .await?;
+
+        // The default exchange ("") routes to a queue of the same name with
+        // no explicit binding; anything else needs `queue` bound to it under
+        // `queue`'s own name, so an `RPCHandle` must be created with `queue`
+        // as its routing key for its requests to arrive here.
+        if !exchange.is_empty() {
+            channel
+                .queue_bind(
+                    queue,
+                    exchange,
+                    queue,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        let consumer = channel
+            .basic_consume(
+                queue,
+                "",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+
+        Ok(Self {
+            channel: channel.clone(),
+            consumer,
+            handler,
+            request_codec: JsonCodec::new(),
+            response_codec: JsonCodec::new(),
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<Req, Resp, F, Fut, HError, CReq, CResp> RPCHandler<Req, Resp, F, CReq, CResp>
+where
+    F: Fn(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, HError>>,
+    CReq: Codec<Req>,
+    CResp: Codec<Resp>,
+{
+    /// Answers requests until the broker closes the consumer. A request
+    /// whose body fails to decode, or whose handler returns `Err`, is
+    /// dropped without a reply rather than stalling the caller forever —
+    /// callers still bound their wait with a timeout on their end.
+    pub async fn run(&mut self) -> Result<(), RPCError<CReq::Error>> {
+        while let Some(delivery) = self.consumer.next().await {
+            let delivery = delivery?;
+            delivery.ack(BasicAckOptions::default()).await?;
+
+            let (Some(reply_to), Some(correlation_id)) = (
+                delivery.properties.reply_to().clone(),
+                delivery.properties.correlation_id().clone(),
+            ) else {
+                continue;
+            };
+
+            let request = match self.request_codec.decode(&delivery.data) {
+                Ok(request) => request,
+                Err(_) => continue,
+            };
+
+            let Ok(response) = (self.handler)(request).await else {
+                continue;
+            };
+
+            let payload = self.response_codec.encode(&response);
+
+            self.channel
+                .basic_publish(
+                    "",
+                    reply_to.as_str(),
+                    BasicPublishOptions::default(),
+                    payload.as_slice(),
+                    BasicProperties::default()
+                        .with_content_type(CResp::CONTENT_TYPE.into())
+                        .with_correlation_id(correlation_id),
+                )
+                .await?
+                .await?;
+        }
+
+        Ok(())
+    }
+}
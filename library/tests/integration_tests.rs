@@ -1,5 +1,5 @@
 use lapin::{options::*, types::FieldTable, Connection, ConnectionProperties};
-use simple_rpc::{self, RPCRoute};
+use library::{RPCHandle, RPCHandler};
 
 pub const INPUT_STR: &str = "This is the input";
 pub const RETURN_STR: &str = "This is the return";
@@ -39,24 +39,24 @@ async fn integration_test_with_external_amqp() {
         .await
         .unwrap();
 
-    let route: RPCRoute<_, _> = RPCRoute::new("", "hello", "hello_response");
-
-    let mut handler = route.handler(&channel_a, handler).await.unwrap();
-
-    let mut handle_controller = route.handle(channel_b).await.unwrap();
+    let mut rpc_handler = RPCHandler::new("", "hello", &channel_a, handler)
+        .await
+        .unwrap();
 
-    let handle = handle_controller.get_handle();
+    let rpc_handle = RPCHandle::<String, String>::new("", "hello", "hello_response", &channel_b)
+        .await
+        .unwrap();
 
     let input = INPUT_STR.to_string();
 
     tokio::select! {
-        request_result = handle.send(&input) => {
-            assert_eq!(request_result.unwrap().unwrap().as_str(), RETURN_STR);
+        request_result = rpc_handle.send(&input) => {
+            assert_eq!(request_result.unwrap().as_str(), RETURN_STR);
         },
-        _handler_result = handler.run() => {
+        _handler_result = rpc_handler.run() => {
             unreachable!()
         }
-        _runner_result = handle_controller.run() => {
+        _runner_result = rpc_handle.run() => {
             unreachable!()
         }
     };
@@ -9,7 +9,11 @@ use syn::{
 };
 
 #[derive(Debug, Default, Eq, PartialEq, FromMeta)]
-struct HooksLapinProducerArgs {}
+struct HooksLapinProducerArgs {
+    /// The `Codec` type (unparameterized) used for every route on the
+    /// trait, e.g. `codec = PreservesCodec`. Defaults to `JsonCodec`.
+    codec: Option<syn::Ident>,
+}
 
 impl HooksLapinProducerArgs {
     pub fn parse(attr: TokenStream) -> Result<Self, TokenStream> {
@@ -28,7 +32,11 @@ impl HooksLapinProducerArgs {
 }
 
 #[derive(Debug, Default, Eq, PartialEq, FromMeta)]
-struct HooksLapinConsumerArgs {}
+struct HooksLapinConsumerArgs {
+    /// The `Codec` type (unparameterized) used for every route on the
+    /// trait, e.g. `codec = PreservesCodec`. Defaults to `JsonCodec`.
+    codec: Option<syn::Ident>,
+}
 
 impl HooksLapinConsumerArgs {
     pub fn parse(attr: TokenStream) -> Result<Self, TokenStream> {
@@ -46,6 +54,13 @@ impl HooksLapinConsumerArgs {
     }
 }
 
+fn codec_type(codec: &Option<syn::Ident>, data_type: &Type) -> TokenStream {
+    match codec {
+        Some(ident) => quote! { #ident<#data_type> },
+        None => quote! { ::easymq::codec::JsonCodec<#data_type> },
+    }
+}
+
 #[derive(Debug, Default, Eq, PartialEq, FromMeta)]
 struct AmqpRouteArgs {
     pub path: Option<syn::Ident>,
@@ -174,7 +189,7 @@ pub fn hooks_lapin_producer(
     attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let _args = match HooksLapinProducerArgs::parse(TokenStream::from(attr)) {
+    let args = match HooksLapinProducerArgs::parse(TokenStream::from(attr)) {
         Ok(val) => val,
         Err(err) => return err.into(),
     };
@@ -198,6 +213,11 @@ pub fn hooks_lapin_producer(
         Err(err) => return err.into(),
     };
 
+    let codec_types: Vec<TokenStream> = data_types
+        .iter()
+        .map(|data_type| codec_type(&args.codec, data_type))
+        .collect();
+
     let impl_trait_ident = Ident::new(&trait_ident.to_string(), Span::call_site());
     let producer_name_string = format!("{}LapinProducer", trait_ident);
     let producer_name = Ident::new(&producer_name_string, Span::call_site());
@@ -207,7 +227,7 @@ pub fn hooks_lapin_producer(
         #item
 
         #vis struct #producer_name<'c> {
-            #(#method_names: ::easymq::lapin::LapinProducer<'static, 'c, #data_types, fn(#data_types) -> Vec<u8>>,)*
+            #(#method_names: ::easymq::lapin::LapinProducer<'static, 'c, #data_types, #codec_types>,)*
         }
 
         impl<'c> ::std::fmt::Debug for #producer_name<'c> {
@@ -241,7 +261,7 @@ pub fn hooks_lapin_consumer(
     attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let _args = match HooksLapinConsumerArgs::parse(TokenStream::from(attr)) {
+    let args = match HooksLapinConsumerArgs::parse(TokenStream::from(attr)) {
         Ok(val) => val,
         Err(err) => return err.into(),
     };
@@ -265,6 +285,17 @@ pub fn hooks_lapin_consumer(
         Err(err) => return err.into(),
     };
 
+    let codec_types: Vec<TokenStream> = data_types
+        .iter()
+        .map(|data_type| codec_type(&args.codec, data_type))
+        .collect();
+
+    // Every route on the trait shares the same codec, so the decode error
+    // type is the same for all of them; borrow the first route's to name it.
+    let codec_error_type = data_types.first().zip(codec_types.first()).map(
+        |(data_type, codec_type)| quote! { <#codec_type as ::easymq::Codec<#data_type>>::Error },
+    );
+
     let impl_trait_ident = Ident::new(&trait_ident.to_string(), Span::call_site());
     let consumer_name_string = format!("{}LapinConsumer", trait_ident);
     let consumer_name = Ident::new(&consumer_name_string, Span::call_site());
@@ -275,7 +306,7 @@ pub fn hooks_lapin_consumer(
 
         #vis struct #consumer_name<'a, TConsumer: #impl_trait_ident + Sync + Send> {
             consumer: &'a mut TConsumer,
-            #(#method_names: ::easymq::lapin::LapinConsumer<#data_types, ::serde_json::Error, fn(Vec<u8>) -> Result<#data_types, ::serde_json::Error>,>,)*
+            #(#method_names: ::easymq::lapin::LapinConsumer<#data_types, #codec_types>,)*
         }
 
         impl<'a, TConsumer: #impl_trait_ident + Sync + Send> ::std::fmt::Debug for #consumer_name<'a, TConsumer> {
@@ -296,7 +327,7 @@ pub fn hooks_lapin_consumer(
                 })
             }
 
-            async fn run(&mut self) -> Result<(), ::easymq::AmqpConsumerError<::lapin::Error, ::serde_json::Error>> {
+            async fn run(&mut self) -> Result<(), ::easymq::AmqpConsumerError<::lapin::Error, #codec_error_type>> {
                 #(let mut #method_names = ::easymq::Consumer::to_stream(&mut self.#method_names);)*
         
                 ::futures::select! {
@@ -305,9 +336,13 @@ pub fn hooks_lapin_consumer(
                         let Some(result) = result else {
                             return Ok(());
                         };
-                        let data = result?;
-    
+                        let (data, handle) = result?;
+
                         TConsumer::#method_names(self.consumer, data).await;
+
+                        ::easymq::DeliveryHandle::ack(handle)
+                            .await
+                            .map_err(::easymq::AmqpConsumerError::ConsumerError)?;
                     }
                 )*
                 }
@@ -0,0 +1,205 @@
+//! Reads `routes.schema` and emits the routes it declares as Rust: an
+//! `AmqpQueueDeclaration` constant per queue plus a producer/consumer trait
+//! pair per route, generated the same shape as a hand-written
+//! `#[hooks_lapin_producer]`/`#[hooks_lapin_consumer]` trait. Included by
+//! `src/lib.rs` via `include!(concat!(env!("OUT_DIR"), "/routes.rs"))`,
+//! mirroring how `proto` includes its prost-generated file.
+
+use std::{env, fs, path::Path};
+
+struct RouteDef {
+    name: String,
+    queue: String,
+    exchange: String,
+    routing_key: String,
+    request: String,
+    response: Option<String>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=routes.schema");
+
+    let schema = fs::read_to_string("routes.schema").expect("failed to read routes.schema");
+    let routes = parse_schema(&schema);
+
+    let generated = routes.iter().map(generate_route).collect::<String>();
+
+    let out_dir = env::var_os("OUT_DIR").expect("OUT_DIR not set");
+    fs::write(Path::new(&out_dir).join("routes.rs"), generated)
+        .expect("failed to write generated routes.rs");
+}
+
+/// Strips `//` comments, then splits the file into `route Name { ... }`
+/// blocks and parses each block's `key: "value",` entries.
+fn parse_schema(schema: &str) -> Vec<RouteDef> {
+    let without_comments: String = schema
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut routes = Vec::new();
+    let mut rest = without_comments.as_str();
+
+    while let Some(route_idx) = rest.find("route ") {
+        rest = &rest[route_idx + "route ".len()..];
+        let open_brace = rest
+            .find('{')
+            .expect("expected `{` after `route <Name>`");
+        let name = rest[..open_brace].trim().to_owned();
+        let close_brace = rest
+            .find('}')
+            .expect("expected closing `}` for route block");
+        let body = &rest[open_brace + 1..close_brace];
+        routes.push(parse_route_body(&name, body));
+        rest = &rest[close_brace + 1..];
+    }
+
+    routes
+}
+
+/// Splits `body` into `key: value` fields on top-level commas, i.e. commas
+/// that are not inside a `"..."` string or a `<...>` generic argument list
+/// (a `request`/`response` value is a bare Rust type, so `HashMap<String,
+/// String>` or `Result<A, B>` must survive as a single field).
+fn split_fields(body: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut depth: u32 = 0;
+    let mut in_string = false;
+    let mut start = 0;
+
+    for (i, c) in body.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '<' if !in_string => depth += 1,
+            '>' if !in_string && depth > 0 => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                fields.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&body[start..]);
+
+    fields
+}
+
+fn parse_route_body(name: &str, body: &str) -> RouteDef {
+    let mut queue = None;
+    let mut exchange = None;
+    let mut routing_key = None;
+    let mut request = None;
+    let mut response = None;
+
+    for field in split_fields(body) {
+        let field = field.trim();
+        if field.is_empty() {
+            continue;
+        }
+        let (key, value) = field
+            .split_once(':')
+            .unwrap_or_else(|| panic!("malformed field `{field}` in route `{name}`"));
+        let key = key.trim();
+        let value = value.trim().trim_matches('"').to_owned();
+
+        match key {
+            "queue" => queue = Some(value),
+            "exchange" => exchange = Some(value),
+            "routing_key" => routing_key = Some(value),
+            "request" => request = Some(value),
+            "response" => response = Some(value),
+            other => panic!("unknown field `{other}` in route `{name}`"),
+        }
+    }
+
+    RouteDef {
+        name: name.to_owned(),
+        queue: queue.unwrap_or_else(|| panic!("route `{name}` is missing `queue`")),
+        exchange: exchange.unwrap_or_else(|| panic!("route `{name}` is missing `exchange`")),
+        routing_key: routing_key
+            .unwrap_or_else(|| panic!("route `{name}` is missing `routing_key`")),
+        request: request.unwrap_or_else(|| panic!("route `{name}` is missing `request`")),
+        response,
+    }
+}
+
+fn generate_route(route: &RouteDef) -> String {
+    let RouteDef {
+        name,
+        queue,
+        exchange,
+        routing_key,
+        request,
+        response,
+    } = route;
+
+    let module = to_snake_case(name);
+    let method = &module;
+
+    let mut out = format!(
+        r#"
+pub mod {module} {{
+    pub const ROUTE: ::easymq::AmqpQueueDeclaration<'static, {request}, ::easymq::codec::JsonCodec<{request}>> =
+        ::easymq::AmqpQueueDeclaration::new(
+            ::easymq::AmqpQueueInformation {{
+                queue_name: "{queue}",
+                exchange: "{exchange}",
+                routing_key: "{routing_key}",
+                binding_patterns: &[],
+            }},
+            ::easymq::codec::JsonCodec::new(),
+        );
+
+    #[::easymq::hooks_lapin_producer]
+    pub trait Producer {{
+        #[amqp_route(path = ROUTE)]
+        async fn {method}(&mut self, {method}: {request});
+    }}
+
+    #[::easymq::hooks_lapin_consumer]
+    pub trait Consumer {{
+        #[amqp_route(path = ROUTE)]
+        async fn {method}(&mut self, {method}: {request});
+    }}
+"#
+    );
+
+    if let Some(response) = response {
+        out.push_str(&format!(
+            r#"
+    /// Declared for pairing with an RPC layer: `{module}::ROUTE` carries the
+    /// request, this carries the `{response}` reply on a `<queue>_response`
+    /// queue of the same name.
+    pub const RESPONSE_ROUTE: ::easymq::AmqpQueueDeclaration<'static, {response}, ::easymq::codec::JsonCodec<{response}>> =
+        ::easymq::AmqpQueueDeclaration::new(
+            ::easymq::AmqpQueueInformation {{
+                queue_name: concat!("{queue}", "_response"),
+                exchange: "{exchange}",
+                routing_key: concat!("{routing_key}", "_response"),
+                binding_patterns: &[],
+            }},
+            ::easymq::codec::JsonCodec::new(),
+        );
+"#
+        ));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() {
+            if i != 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,10 @@
+//! Typed routes generated from `routes.schema` by `build.rs`.
+//!
+//! Each `route` declaration in the schema becomes a module holding the
+//! route's `AmqpQueueDeclaration` constant(s) and a matching producer/
+//! consumer trait pair, so a messaging topology can be declared once in
+//! the schema document instead of as hand-written boilerplate.
+
+pub mod routes {
+    include!(concat!(env!("OUT_DIR"), "/routes.rs"));
+}